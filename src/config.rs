@@ -0,0 +1,116 @@
+/*
+ * Copyright (c) 2023 Marcus Butler
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! TOML configuration file support. Declares stages, before-sleep/after-resume
+//! commands, and inhibition rules with explicit `command`/`args`
+//! arrays, instead of the fragile space-split strings `-c` takes on the
+//! command line.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Config {
+    #[serde(default, rename = "stage")]
+    pub stages: Vec<StageConfig>,
+    pub before_sleep: Option<CommandConfig>,
+    pub after_resume: Option<CommandConfig>,
+    pub inhibit: Option<InhibitConfig>,
+}
+
+/// `[inhibit]`: conditions that hold off firing a stage even once input and
+/// Wayland both look idle.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct InhibitConfig {
+    pub max_load: Option<f64>,
+    #[serde(default)]
+    pub processes: Vec<String>,
+    #[serde(default)]
+    pub block_on_audio: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StageConfig {
+    pub timeout: u64,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub resume_command: Option<String>,
+    #[serde(default)]
+    pub resume_args: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CommandConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Resolve the config file path: `explicit` (from `--config`) if given,
+/// otherwise `$XDG_CONFIG_HOME/idlewatcher/config.toml`, falling back to
+/// `$HOME/.config/idlewatcher/config.toml`.
+pub fn config_path(explicit: Option<&str>) -> Option<PathBuf> {
+    if let Some(p) = explicit {
+        return Some(PathBuf::from(p));
+    }
+
+    let config_home = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+    };
+
+    Some(config_home.join("idlewatcher").join("config.toml"))
+}
+
+/// Load and parse the config file at `path`. Returns `None` if the file
+/// doesn't exist or can't be read or parsed. Read and parse failures are
+/// logged; a missing file is logged only when `explicit` is set -- a missing
+/// config file is not an error in itself, since every setting has a
+/// command-line equivalent, but a missing `explicit` (`--config`) path almost
+/// always means the user mistyped it, so that case is still worth a warning.
+pub fn load(path: &Path, explicit: bool) -> Option<Config> {
+    let text = match fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if explicit {
+                eprintln!("Config file {} not found", path.display());
+            }
+            return None;
+        }
+        Err(e) => {
+            eprintln!("Cannot read config file {}: {:?}", path.display(), e);
+            return None;
+        }
+    };
+
+    match toml::from_str(&text) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            eprintln!("Cannot parse config file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
@@ -0,0 +1,147 @@
+/*
+ * Copyright (c) 2023 Marcus Butler
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! Runtime conditions that should hold off firing an idle stage even though
+//! input and Wayland both look idle: a high load average (a long compile),
+//! a named process running (e.g. a backup job), or active audio playback.
+
+use std::fs;
+use std::path::Path;
+
+use crate::config::InhibitConfig;
+
+/// Return `Some(reason)` if any enabled predicate in `config` currently
+/// holds, else `None`. Checked in a fixed order: load average, then
+/// processes (in list order), then audio.
+pub fn check(config: &InhibitConfig) -> Option<String> {
+    if let Some(max_load) = config.max_load {
+        if let Some(load) = load_average_1m() {
+            if load > max_load {
+                return Some(format!(
+                    "1-minute load average {:.2} exceeds max_load {:.2}",
+                    load, max_load
+                ));
+            }
+        }
+    }
+
+    for pattern in &config.processes {
+        if process_running(pattern) {
+            return Some(format!("a process matching \"{}\" is running", pattern));
+        }
+    }
+
+    if config.block_on_audio && audio_playing() {
+        return Some(String::from("audio is currently playing"));
+    }
+
+    None
+}
+
+fn load_average_1m() -> Option<f64> {
+    let text = fs::read_to_string("/proc/loadavg").ok()?;
+    text.split_whitespace().next()?.parse().ok()
+}
+
+/// True if any process under /proc has a `comm` matching `pattern` exactly
+/// (the same name `pgrep -x` or `/proc/<pid>/comm` would report).
+fn process_running(pattern: &str) -> bool {
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    for entry in entries.flatten() {
+        let is_pid = entry
+            .file_name()
+            .to_str()
+            .map(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+            .unwrap_or(false);
+
+        if !is_pid {
+            continue;
+        }
+
+        if let Ok(comm) = fs::read_to_string(entry.path().join("comm")) {
+            if comm.trim() == pattern {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// True if any ALSA PCM substream under /proc/asound is in the RUNNING
+/// state. PipeWire and PulseAudio both run their outputs through ALSA, so
+/// this catches audio playback regardless of which sound server mixed it.
+fn audio_playing() -> bool {
+    let cards = match fs::read_dir("/proc/asound") {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    cards
+        .flatten()
+        .filter(|card| starts_with(&card.path(), "card"))
+        .any(|card| card_has_running_pcm(&card.path()))
+}
+
+fn card_has_running_pcm(card_path: &Path) -> bool {
+    let pcms = match fs::read_dir(card_path) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    for pcm in pcms.flatten().filter(|p| is_playback_pcm(&p.path())) {
+        let subs = match fs::read_dir(pcm.path()) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for sub in subs.flatten().filter(|s| starts_with(&s.path(), "sub")) {
+            let status = fs::read_to_string(sub.path().join("status")).unwrap_or_default();
+            if status.contains("RUNNING") {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+fn starts_with(path: &Path, prefix: &str) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.starts_with(prefix))
+        .unwrap_or(false)
+}
+
+/// True for playback substreams (`pcmXp`), false for capture (`pcmXc`) -- a
+/// running microphone/capture stream isn't "audio playback" and shouldn't
+/// block suspend on its own.
+fn is_playback_pcm(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.starts_with("pcm") && n.ends_with('p'))
+        .unwrap_or(false)
+}
@@ -23,10 +23,15 @@
 use std::env;
 use std::io::{ErrorKind};
 use std::fs;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
 use std::os::unix::fs::MetadataExt;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration,SystemTime};
-use std::thread::sleep;
+use calloop::generic::Generic;
+use calloop::timer::{Timer, TimeoutAction};
+use calloop::{EventLoop, Interest, LoopHandle, LoopSignal, Mode, PostAction};
 use getopts::Options;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
 use wayrs_client::{Connection, global::GlobalsExt, EventCtx, IoMode};
 use wayrs_utils::seats::*;
 use wayrs_protocols::ext_idle_notify_v1::{
@@ -34,17 +39,111 @@ use wayrs_protocols::ext_idle_notify_v1::{
 };
 use utmp_rs::UtmpEntry;
 
+mod config;
+mod inhibit;
+mod sleep;
+use config::InhibitConfig;
+use sleep::SleepInhibitor;
+
+/// Shortest and longest delay between idle checks. A stage's own timeout can
+/// narrow this window, but we never poll faster than `MIN_CHECK_INTERVAL` nor
+/// let a fully-fired watcher sleep longer than `MAX_CHECK_INTERVAL`, since that
+/// is also how quickly we notice a stage resuming.
+const MIN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Wraps a raw fd so it can be registered with calloop, which wants an `AsFd`.
+/// The fd is borrowed, not owned -- `Connection`/`SleepInhibitor` still own it.
+struct BorrowedRawFd(RawFd);
+
+impl AsFd for BorrowedRawFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+// Signal handlers only set these flags -- all the real work (formatting,
+// printing, tearing down connections) happens on the main thread once the
+// event loop notices a flag is set, since that work isn't async-signal-safe.
+static STATUS_DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_status_signal(_sig: i32) {
+    STATUS_DUMP_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn on_shutdown_signal(_sig: i32) {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Install SIGUSR1 (status dump) and SIGINT/SIGTERM (graceful shutdown)
+/// handlers. The handlers do nothing but flip an `AtomicBool`; the event loop
+/// polls them once per tick.
+fn install_signal_handlers() {
+    let status_action = SigAction::new(SigHandler::Handler(on_status_signal), SaFlags::empty(), SigSet::empty());
+    let shutdown_action = SigAction::new(SigHandler::Handler(on_shutdown_signal), SaFlags::empty(), SigSet::empty());
+
+    unsafe {
+        sigaction(Signal::SIGUSR1, &status_action).expect("Cannot install SIGUSR1 handler");
+        sigaction(Signal::SIGINT, &shutdown_action).expect("Cannot install SIGINT handler");
+        sigaction(Signal::SIGTERM, &shutdown_action).expect("Cannot install SIGTERM handler");
+    }
+}
+
 #[link(name = "c")]
 extern "C" {
     fn getuid() -> u32;
 }
 
+/// A single idle stage: once input/Wayland idle time exceeds `timeout` seconds,
+/// `idle_cmd` is run; if activity later resumes, `resume_cmd` (if any) is run once.
+#[derive(Debug, Clone)]
+struct Stage {
+    timeout: u64,
+    idle_cmd: String,
+    idle_cmd_args: Vec<String>,
+    resume_cmd: Option<String>,
+    resume_cmd_args: Vec<String>,
+    fired: bool,
+    wayland_idle: bool,
+    // Set once we've logged that this stage is exceeded-but-inhibited, so we
+    // don't repeat the message every tick for as long as inhibition holds.
+    inhibit_warned: bool,
+}
+
 #[derive(Debug)]
 struct State {
-    idle: bool,
+    // Per-stage Wayland idle flag: true only once every seat bound at connect
+    // time has gone idle for that stage's timeout.
+    stage_idle: Vec<bool>,
+    // One notification per (seat, stage) pair; notification_stage/seat_idle
+    // are parallel vecs recording which stage each notification belongs to
+    // and whether that particular seat is currently idle for it.
+    notifications: Vec<ExtIdleNotificationV1>,
+    notification_stage: Vec<usize>,
+    seat_idle: Vec<bool>,
     seats: Seats,
 }
 
+impl State {
+    fn stage_for(&mut self, notification: ExtIdleNotificationV1) -> Option<usize> {
+        self.notifications.iter().position(|n| *n == notification)
+    }
+
+    /// Record this notification's new idle state, then recompute its stage's
+    /// aggregate idle flag as the AND of every seat subscribed to that stage.
+    fn set_seat_idle(&mut self, idx: usize, idle: bool) {
+        self.seat_idle[idx] = idle;
+        let stage = self.notification_stage[idx];
+        self.stage_idle[stage] = self
+            .notification_stage
+            .iter()
+            .zip(self.seat_idle.iter())
+            .filter(|(s, _)| **s == stage)
+            .all(|(_, idle)| *idle);
+    }
+}
+
 impl SeatHandler for State {
     fn get_seats(&mut self) -> &mut Seats {
         &mut self.seats
@@ -56,146 +155,450 @@ enum WaylandState {
     Disabled
 }
 
+/// The event loop's shared data: the stage list, the Wayland connection (if
+/// any), the logind sleep inhibitor (if any), and a handle back into the loop
+/// so callbacks can register/deregister sources (e.g. on Wayland reconnect).
+struct LoopData {
+    stages: Vec<Stage>,
+    wayland: WaylandState,
+    wayland_source: Option<calloop::RegistrationToken>,
+    sleep_inhibitor: Option<SleepInhibitor>,
+    inhibit: Option<InhibitConfig>,
+    handle: LoopHandle<'static, LoopData>,
+    shutdown_signal: LoopSignal,
+    // Per-tty idle times from the most recent check_idle, kept around so the
+    // SIGUSR1 status dump can report them individually instead of collapsing
+    // them down to the single minimum (most_active) used for firing stages.
+    tty_idle: Vec<(String, u64)>,
+}
+
 const DEFAULT_SLEEP_CMD: &str = "/usr/bin/systemctl";
 const DEFAULT_SLEEP_ARGS: &str = "suspend";
 const DEFAULT_IDLE_TIME: u64 = 3600;
 
-fn main() {
-    let mut idle_limit: u64 = DEFAULT_IDLE_TIME; // Time in seconds.
-    let mut idle_cmd: String = String::from(DEFAULT_SLEEP_CMD);
-    let mut idle_cmd_args: Vec<String> = vec![];
-    let mut wayland_idle: bool = false;
+/// Parse a space-split command string into a (command, args) pair.
+fn split_cmd(cmd: &str) -> (String, Vec<String>) {
+    let split = cmd.split(' ').collect::<Vec<&str>>();
+    let command = String::from(split[0]);
+    let args = split[1..].iter().map(|s| s.to_string()).collect();
+    (command, args)
+}
+
+/// Build the stage list from the repeated `-t`/`-c` (and optional `-r`) options.
+/// The Nth `-t` and the Nth `-c` define the Nth stage; the Nth `-r`, if there
+/// is one, supplies that stage's resume command. `-r` options are matched by
+/// count, not by where they appear among the `-t`/`-c` options.
+fn stages_from_cli(matches: &getopts::Matches) -> Vec<Stage> {
+    let timeouts = matches.opt_strs("t");
+    let commands = matches.opt_strs("c");
+    let resumes = matches.opt_strs("r");
+
+    if timeouts.len() != commands.len() {
+        panic!("-t and -c must be given the same number of times, one pair per stage");
+    }
+
+    timeouts
+        .iter()
+        .zip(commands.iter())
+        .enumerate()
+        .map(|(i, (t, c))| {
+            let timeout: u64 = t.parse().expect("Timeout not an integer");
+            let (idle_cmd, idle_cmd_args) = split_cmd(c);
+            let (resume_cmd, resume_cmd_args) = match resumes.get(i) {
+                Some(r) => {
+                    let (cmd, args) = split_cmd(r);
+                    (Some(cmd), args)
+                }
+                None => (None, vec![]),
+            };
+
+            Stage {
+                timeout,
+                idle_cmd,
+                idle_cmd_args,
+                resume_cmd,
+                resume_cmd_args,
+                fired: false,
+                wayland_idle: false,
+                inhibit_warned: false,
+            }
+        })
+        .collect()
+}
+
+/// Build the stage list from a parsed config file's `[[stage]]` entries.
+fn stages_from_config(config: &config::Config) -> Vec<Stage> {
+    config
+        .stages
+        .iter()
+        .map(|s| Stage {
+            timeout: s.timeout,
+            idle_cmd: s.command.clone(),
+            idle_cmd_args: s.args.clone(),
+            resume_cmd: s.resume_command.clone(),
+            resume_cmd_args: s.resume_args.clone(),
+            fired: false,
+            wayland_idle: false,
+            inhibit_warned: false,
+        })
+        .collect()
+}
+
+/// Resolve the stage list: command-line `-t`/`-c` stages win outright if any
+/// were given, otherwise the config file's `[[stage]]` entries are used,
+/// falling back to a single default stage if neither is present.
+fn build_stages(matches: &getopts::Matches, config: Option<&config::Config>) -> Vec<Stage> {
+    if matches.opt_present("t") || matches.opt_present("c") {
+        return stages_from_cli(matches);
+    }
+
+    if let Some(config) = config {
+        if !config.stages.is_empty() {
+            return stages_from_config(config);
+        }
+    }
+
+    vec![Stage {
+        timeout: DEFAULT_IDLE_TIME,
+        idle_cmd: String::from(DEFAULT_SLEEP_CMD),
+        idle_cmd_args: DEFAULT_SLEEP_ARGS.split(' ').map(|s| s.to_string()).collect(),
+        resume_cmd: None,
+        resume_cmd_args: vec![],
+        fired: false,
+        wayland_idle: false,
+        inhibit_warned: false,
+    }]
+}
 
+fn main() {
     let args: Vec<String> = env::args().collect();
     let mut opts = Options::new();
-    opts.optopt("t", "timeout", "Idle Timeout", "");
-    opts.optopt("c", "command", "Idle Command", "");
+    opts.optmulti("t", "timeout", "Idle Timeout (repeatable, one per stage)", "");
+    opts.optmulti("c", "command", "Idle Command (repeatable, one per stage)", "");
+    opts.optmulti("r", "resume", "Resume Command for the stage at the same position", "");
+    opts.optopt("", "before-sleep", "Command to run just before the system suspends", "");
+    opts.optopt("", "after-resume", "Command to run just after the system resumes", "");
+    opts.optopt("", "config", "Path to a TOML config file (default: $XDG_CONFIG_HOME/idlewatcher/config.toml)", "");
     opts.optflag("h", "help", "Usage information");
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => { m }
         Err(f) => { panic!("{}", f.to_string()); }
-    }; 
+    };
 
     if matches.opt_present("h") {
-        eprintln!("Usage: idlewatcher -t timeout -c command");
-        eprintln!("\t-t timeout\tAn integer specifying the idle timeout in seconds.  Default: 3600 seconds.");
-        eprintln!("\t-c command\tThe command to execute when the idle timeout is reached.  Defaults to systemctl suspend.");
+        eprintln!("Usage: idlewatcher -t timeout -c command [-r resume] [-t timeout -c command [-r resume] ...]");
+        eprintln!("\t-t timeout\tAn integer specifying the idle timeout in seconds for this stage.");
+        eprintln!("\t-c command\tThe command to execute when this stage's idle timeout is reached.");
+        eprintln!("\t\t\t-t and -c must be given the same number of times.  With neither (and no config file stages),");
+        eprintln!("\t\t\ta single stage runs systemctl suspend after 3600 seconds.");
+        eprintln!("\t-r resume\tA command to execute once activity resumes after this stage has fired.  Optional;");
+        eprintln!("\t\t\tthe Nth -r belongs to the Nth stage.");
+        eprintln!("\t\t\tRepeat -t/-c (and optionally -r) to define additional stages, e.g. dim the screen before suspending.");
+        eprintln!("\t--before-sleep cmd\tCommand to run right before the system suspends (via a logind delay inhibitor).");
+        eprintln!("\t--after-resume cmd\tCommand to run right after the system resumes from suspend.");
+        eprintln!("\t--config path\tPath to a TOML config file.  Defaults to $XDG_CONFIG_HOME/idlewatcher/config.toml.");
+        eprintln!("\t\t\tCommand-line options above override the matching config file setting.");
         return;
     }
 
-    if matches.opt_present("t") {
-        idle_limit = matches.opt_str("t").unwrap().parse().expect("Timeout not an integer"); 
+    let explicit_config = matches.opt_str("config");
+    let config = config::config_path(explicit_config.as_deref())
+        .and_then(|path| config::load(&path, explicit_config.is_some()));
+
+    let mut stages = build_stages(&matches, config.as_ref());
+
+    for stage in &stages {
+        println!("Timeout: {} Idle Command: {} {:?}", stage.timeout, stage.idle_cmd, stage.idle_cmd_args);
     }
 
-    if matches.opt_present("c") {
-        match matches.opt_str("c") {
-            Some(cmd) => {
-                let split = cmd.split(' ').collect::<Vec<&str>>();
+    let before_sleep = matches
+        .opt_str("before-sleep")
+        .map(|c| split_cmd(&c))
+        .or_else(|| config.as_ref().and_then(|c| c.before_sleep.clone()).map(|c| (c.command, c.args)));
+    let after_resume = matches
+        .opt_str("after-resume")
+        .map(|c| split_cmd(&c))
+        .or_else(|| config.as_ref().and_then(|c| c.after_resume.clone()).map(|c| (c.command, c.args)));
+    let sleep_inhibitor = SleepInhibitor::new(before_sleep, after_resume);
+    let inhibit = config.as_ref().and_then(|c| c.inhibit.clone());
+
+    install_signal_handlers();
+
+    let mut event_loop: EventLoop<LoopData> =
+        EventLoop::try_new().expect("Cannot create event loop");
+    let handle = event_loop.handle();
+    let shutdown_signal = event_loop.get_signal();
+
+    let mut data = LoopData {
+        stages,
+        wayland: WaylandState::Disabled,
+        wayland_source: None,
+        sleep_inhibitor,
+        inhibit,
+        handle: handle.clone(),
+        shutdown_signal,
+        tty_idle: Vec::new(),
+    };
 
-                idle_cmd = String::from(split[0]);
-                for elem in split[1..].iter() {
-                    idle_cmd_args.push(elem.to_string());
-                }
-            },
-            _ => {}
-        }
-    } else {
-        let split = DEFAULT_SLEEP_ARGS.split(' ').collect::<Vec<&str>>();
-        for elem in split.iter() {
-            idle_cmd_args.push(elem.to_string());
-        }
+    connect_wayland(&mut data);
+
+    if let Some(fd) = data.sleep_inhibitor.as_ref().map(|i| i.as_raw_fd()) {
+        handle
+            .insert_source(
+                Generic::new(BorrowedRawFd(fd), Interest::READ, Mode::Level),
+                |_, _, data: &mut LoopData| {
+                    if let Some(inhibitor) = data.sleep_inhibitor.as_mut() {
+                        inhibitor.handle_events();
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .expect("Cannot register sleep inhibitor fd");
     }
 
-    println!("Timeout: {} Idle Command: {} {:?}", idle_limit, idle_cmd, idle_cmd_args);
-
-    // Wayland setup
-    let mut wayland: WaylandState = WaylandState::Disabled;
-    
-    loop {
-        let mut most_active: u64 = u64::MAX;
-
-        if let Ok(entries) = utmp_rs::parse_from_path("/var/run/utmp") {
-            let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).expect("Error getting system time").as_secs();
-
-            for entry in entries {
-                match entry {
-                    UtmpEntry::UserProcess{line,..} => {
-                        let filename = format!("/dev/{}", line);
-                        let atime = fs::metadata(filename.clone()).unwrap().atime();
-                        let idle_time = now - atime as u64;
-                        
-                        if idle_time < most_active {
-                            most_active = idle_time;
-                        }
-                    },
-                    _ => {}
-                }
+    schedule_check(&handle, Duration::from_secs(0));
+
+    event_loop
+        .run(None, &mut data, |_| {})
+        .expect("Event loop error");
+}
+
+/// (Re-)register a timer source that checks idle state and reschedules
+/// itself for whatever interval is relevant to the current stage list.
+fn schedule_check(handle: &LoopHandle<'static, LoopData>, after: Duration) {
+    handle
+        .insert_source(Timer::from_duration(after), |_, _, data| {
+            let most_active = check_idle(data);
+            TimeoutAction::ToDuration(next_check_interval(&data.stages, most_active))
+        })
+        .expect("Cannot register idle-check timer");
+}
+
+/// How long until we next need to look at utmp/atime again: the smallest
+/// remaining time to any unfired stage's timeout, clamped to
+/// [MIN_CHECK_INTERVAL, MAX_CHECK_INTERVAL] so we still notice resumes
+/// promptly once every stage has fired.
+fn next_check_interval(stages: &[Stage], most_active: u64) -> Duration {
+    let mut interval = MAX_CHECK_INTERVAL;
+
+    for stage in stages {
+        if !stage.fired {
+            let remaining = Duration::from_secs(stage.timeout.saturating_sub(most_active));
+            if remaining < interval {
+                interval = remaining;
             }
         }
+    }
 
-        let mut wayland_error = false;
-
-        match wayland {
-            WaylandState::Enabled((ref mut conn, ref mut state)) => {
-                let _ = conn.flush(IoMode::NonBlocking);
-                match conn.recv_events(IoMode::NonBlocking) {
-                    Err(e) => {
-                        if e.kind() != ErrorKind::WouldBlock {
-                            wayland_error = true;
-                            println!("Unexpected error: {:?}", e);
-                        }
-                    },
-                    _ => { }
-                }
+    interval.clamp(MIN_CHECK_INTERVAL, MAX_CHECK_INTERVAL)
+}
 
-                conn.dispatch_events(state);
-                wayland_idle = state.idle;
-            },
-            WaylandState::Disabled => {
-                wayland_error = true;
+/// Compute current idle time from utmp/atime, fire/resume stages accordingly,
+/// and return `most_active` so the caller can size the next timer deadline.
+fn check_idle(data: &mut LoopData) -> u64 {
+    if SHUTDOWN_REQUESTED.swap(false, Ordering::SeqCst) {
+        shutdown(data);
+        return 0;
+    }
+
+    let mut most_active: u64 = u64::MAX;
+    data.tty_idle.clear();
+
+    if let Ok(entries) = utmp_rs::parse_from_path("/var/run/utmp") {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).expect("Error getting system time").as_secs();
+
+        for entry in entries {
+            match entry {
+                UtmpEntry::UserProcess{line,..} => {
+                    let filename = format!("/dev/{}", line);
+                    let atime = fs::metadata(filename.clone()).unwrap().atime();
+                    let idle_time = now - atime as u64;
+
+                    data.tty_idle.push((line, idle_time));
+
+                    if idle_time < most_active {
+                        most_active = idle_time;
+                    }
+                },
+                _ => {}
             }
         }
+    }
 
-        // You would think the most natural way to do this would be to call this in the error block above, but you can't do that
-        // because wayland ends up being borrowed there.
-        if wayland_error == true {
-            wayland = wayland_connect(idle_limit);
-        }
-        
-        if most_active > idle_limit && wayland_idle == true {
-            if most_active > idle_limit {
-                eprintln!("Exceeded idle time due to tty atime");
+    match data.wayland {
+        WaylandState::Enabled((_, ref state)) => {
+            for (stage, idle) in data.stages.iter_mut().zip(state.stage_idle.iter()) {
+                stage.wayland_idle = *idle;
             }
-            if wayland_idle == true {
-                eprintln!("Exceeded idle time due to Wayland idle notification");
+        }
+        // No fd is registered while disconnected, so nothing else will ever
+        // retry this -- the old busy-poll loop retried every tick, and we
+        // need to keep doing that or a compositor that isn't up yet (or a
+        // WAYLAND_DISPLAY that hasn't been exported yet) leaves idlewatcher
+        // permanently unable to fire any stage.
+        WaylandState::Disabled => connect_wayland(data),
+    }
+
+    if STATUS_DUMP_REQUESTED.swap(false, Ordering::SeqCst) {
+        dump_status(data, most_active);
+    }
+
+    // Checked once per tick (not once per stage): a compile running or music
+    // playing should hold off every stage, not just the first one checked.
+    let inhibited = data.inhibit.as_ref().and_then(inhibit::check);
+
+    for stage in data.stages.iter_mut() {
+        let exceeded = most_active > stage.timeout && stage.wayland_idle;
+
+        if exceeded && !stage.fired {
+            if let Some(reason) = &inhibited {
+                if !stage.inhibit_warned {
+                    eprintln!("Stage ({}s) exceeded idle time but firing is inhibited: {}", stage.timeout, reason);
+                    stage.inhibit_warned = true;
+                }
+                continue;
             }
-            
-            wayland_idle = false; // Reset Wayland idle timer.
 
-            match std::process::Command::new(idle_cmd.clone())
-                .args(idle_cmd_args.clone())
+            stage.inhibit_warned = false;
+            eprintln!("Stage ({}s) exceeded idle time", stage.timeout);
+            stage.fired = true;
+
+            match std::process::Command::new(stage.idle_cmd.clone())
+                .args(stage.idle_cmd_args.clone())
                 .stdout(std::process::Stdio::piped())
                 .stderr(std::process::Stdio::piped())
                 .spawn() {
                     Ok(_) => {}
-                    Err(e) => { eprintln!("Error running sleep command: {:?}", e); }
+                    Err(e) => { eprintln!("Error running idle command: {:?}", e); }
                 }
+        } else if !exceeded && stage.fired {
+            stage.fired = false;
+
+            if let Some(resume_cmd) = stage.resume_cmd.clone() {
+                match std::process::Command::new(resume_cmd)
+                    .args(stage.resume_cmd_args.clone())
+                    .stdout(std::process::Stdio::piped())
+                    .stderr(std::process::Stdio::piped())
+                    .spawn() {
+                        Ok(_) => {}
+                        Err(e) => { eprintln!("Error running resume command: {:?}", e); }
+                    }
+            }
+        } else if !exceeded {
+            stage.inhibit_warned = false;
         }
-        
-        sleep(Duration::from_secs(5));
+    }
+
+    most_active
+}
+
+/// Print a human-readable snapshot of current state to stderr in response to
+/// SIGUSR1, without disturbing the loop.
+fn dump_status(data: &LoopData, most_active: u64) {
+    eprintln!("--- idlewatcher status ---");
+    eprintln!("TTY idle time: {}s", if most_active == u64::MAX { 0 } else { most_active });
+
+    for (tty, idle_time) in &data.tty_idle {
+        eprintln!("  tty {} idle time: {}s", tty, idle_time);
+    }
+
+    eprintln!("Wayland connected: {}", matches!(data.wayland, WaylandState::Enabled(_)));
+
+    for stage in &data.stages {
+        let remaining = stage.timeout.saturating_sub(most_active);
+        eprintln!(
+            "  stage timeout={}s fired={} wayland_idle={} time_remaining={}s",
+            stage.timeout, stage.fired, stage.wayland_idle, remaining
+        );
+    }
+}
+
+/// Tear down the Wayland connection and logind inhibitor, then stop the
+/// event loop, in response to SIGINT/SIGTERM.
+fn shutdown(data: &mut LoopData) {
+    eprintln!("Shutting down...");
+
+    if let Some(token) = data.wayland_source.take() {
+        data.handle.remove(token);
+    }
+    data.wayland = WaylandState::Disabled;
+
+    // Dropping the inhibitor closes its fd, releasing the logind delay lock.
+    data.sleep_inhibitor = None;
+
+    data.shutdown_signal.stop();
+}
+
+/// Connect (or reconnect) to the Wayland compositor and register its fd with
+/// the event loop. Safe to call with a stale `wayland_source` still present --
+/// it is removed first.
+fn connect_wayland(data: &mut LoopData) {
+    if let Some(token) = data.wayland_source.take() {
+        data.handle.remove(token);
+    }
+
+    data.wayland = wayland_connect(&data.stages);
+
+    let fd = match data.wayland {
+        WaylandState::Enabled((ref conn, _)) => conn.as_raw_fd(),
+        WaylandState::Disabled => return,
+    };
+
+    let token = data
+        .handle
+        .insert_source(
+            Generic::new(BorrowedRawFd(fd), Interest::READ, Mode::Level),
+            |_, _, data: &mut LoopData| {
+                on_wayland_readable(data);
+                Ok(PostAction::Continue)
+            },
+        )
+        .expect("Cannot register Wayland fd");
+
+    data.wayland_source = Some(token);
+}
+
+/// Service a readable Wayland connection; reconnect on any error other than
+/// "nothing to read right now".
+fn on_wayland_readable(data: &mut LoopData) {
+    let mut wayland_error = false;
+
+    if let WaylandState::Enabled((ref mut conn, ref mut state)) = data.wayland {
+        let _ = conn.flush(IoMode::NonBlocking);
+        match conn.recv_events(IoMode::NonBlocking) {
+            Err(e) => {
+                if e.kind() != ErrorKind::WouldBlock {
+                    wayland_error = true;
+                    println!("Unexpected error: {:?}", e);
+                }
+            },
+            _ => { }
+        }
+
+        conn.dispatch_events(state);
+    }
+
+    if wayland_error {
+        connect_wayland(data);
     }
 }
 
 fn way_idle_cb(e: EventCtx<State, ExtIdleNotificationV1>) {
-    println!("Pre event handling Wayland idle state: {}", e.state.idle);
+    let proxy = e.proxy;
+
+    let idx = match e.state.stage_for(proxy) {
+        Some(idx) => idx,
+        None => return,
+    };
+
     match e.event {
         ext_idle_notification_v1::Event::Idled => {
-            e.state.idle = true;
+            e.state.set_seat_idle(idx, true);
         },
 
         ext_idle_notification_v1::Event::Resumed => {
-            e.state.idle = false;
+            e.state.set_seat_idle(idx, false);
         },
 
         _ => {
@@ -204,7 +607,7 @@ fn way_idle_cb(e: EventCtx<State, ExtIdleNotificationV1>) {
     }
 }
 
-fn wayland_connect(idle_limit: u64) -> WaylandState {
+fn wayland_connect(stages: &[Stage]) -> WaylandState {
     let mut conn: Connection<_>;
     let globals: Vec<wayrs_client::protocol::wl_registry::GlobalArgs>;
 
@@ -253,7 +656,10 @@ fn wayland_connect(idle_limit: u64) -> WaylandState {
     }
 
     let mut state = State {
-        idle: false,
+        stage_idle: vec![false; stages.len()],
+        notifications: vec![],
+        notification_stage: vec![],
+        seat_idle: vec![],
         seats: Seats::bind(&mut conn, &globals),
     };
 
@@ -276,8 +682,27 @@ fn wayland_connect(idle_limit: u64) -> WaylandState {
         }
     }
 
-    for seat in state.get_seats().iter() {
-        idle_notifier.get_idle_notification_with_cb(&mut conn, idle_limit as u32 * 1000, seat, way_idle_cb);
+    // Subscribe every seat to every stage -- a stage only counts as idle (see
+    // `State::set_seat_idle`) once all of them have gone idle for its timeout,
+    // so multi-seat setups don't have all but one seat's input ignored.
+    let seats: Vec<_> = state.get_seats().iter().collect();
+    if seats.is_empty() {
+        eprintln!("No Wayland seat available; cannot subscribe to idle notifications.");
+        return WaylandState::Disabled;
+    }
+
+    for seat in seats {
+        for (stage_idx, stage) in stages.iter().enumerate() {
+            let notification = idle_notifier.get_idle_notification_with_cb(
+                &mut conn,
+                stage.timeout as u32 * 1000,
+                seat,
+                way_idle_cb,
+            );
+            state.notifications.push(notification);
+            state.notification_stage.push(stage_idx);
+            state.seat_idle.push(false);
+        }
     }
 
     WaylandState::Enabled((conn, state))
@@ -0,0 +1,179 @@
+/*
+ * Copyright (c) 2023 Marcus Butler
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! systemd-logind sleep inhibitor: lets idlewatcher run commands right before
+//! the system suspends and right after it resumes, even when suspend is
+//! triggered externally (lid close, `systemctl suspend` from another session).
+
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+use dbus::arg::OwnedFd;
+use dbus::blocking::Connection;
+use dbus::channel::Channel;
+use dbus::message::Message;
+
+const LOGIN1_DEST: &str = "org.freedesktop.login1";
+const LOGIN1_PATH: &str = "/org/freedesktop/login1";
+const LOGIN1_MANAGER_IFACE: &str = "org.freedesktop.login1.Manager";
+const CALL_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// Holds the system bus connection and the current delay-inhibitor, if any.
+/// While `inhibitor` is `Some`, logind will delay an actual suspend until the
+/// fd is closed (or the delay lock watchdog times out).
+pub struct SleepInhibitor {
+    conn: Connection,
+    inhibitor: Option<OwnedFd>,
+    before_sleep: Option<(String, Vec<String>)>,
+    after_resume: Option<(String, Vec<String>)>,
+}
+
+impl SleepInhibitor {
+    /// Connect to the system bus and take out an initial delay inhibitor.
+    /// Returns `None` (logging why) if the bus is unreachable, so the rest of
+    /// idlewatcher can keep running without sleep hooks.
+    pub fn new(
+        before_sleep: Option<(String, Vec<String>)>,
+        after_resume: Option<(String, Vec<String>)>,
+    ) -> Option<SleepInhibitor> {
+        let conn = match Connection::new_system() {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Cannot connect to system D-Bus: {:?}", e);
+                return None;
+            }
+        };
+
+        let mut inhibitor = SleepInhibitor {
+            conn,
+            inhibitor: None,
+            before_sleep,
+            after_resume,
+        };
+
+        if let Err(e) = inhibitor.acquire() {
+            eprintln!("Cannot acquire logind sleep inhibitor: {:?}", e);
+            return None;
+        }
+
+        if let Err(e) = inhibitor.subscribe() {
+            eprintln!("Cannot subscribe to PrepareForSleep: {:?}", e);
+            return None;
+        }
+
+        Some(inhibitor)
+    }
+
+    /// Call `Manager.Inhibit(what="sleep", mode="delay")` and stash the
+    /// resulting fd. Holding it open is what delays the actual suspend.
+    fn acquire(&mut self) -> Result<(), dbus::Error> {
+        let proxy = self.conn.with_proxy(LOGIN1_DEST, LOGIN1_PATH, CALL_TIMEOUT);
+        let (fd,): (OwnedFd,) = proxy.method_call(
+            LOGIN1_MANAGER_IFACE,
+            "Inhibit",
+            (
+                "sleep",
+                "idlewatcher",
+                "run before-sleep/after-resume commands",
+                "delay",
+            ),
+        )?;
+        self.inhibitor = Some(fd);
+        Ok(())
+    }
+
+    fn subscribe(&self) -> Result<(), dbus::Error> {
+        self.conn.add_match_no_cb(&format!(
+            "type='signal',interface='{}',member='PrepareForSleep'",
+            LOGIN1_MANAGER_IFACE
+        ))
+    }
+
+    /// The bus's underlying fd, for integrating into the main loop's poll set.
+    pub fn as_raw_fd(&self) -> RawFd {
+        self.conn.channel().as_raw_fd()
+    }
+
+    fn channel(&self) -> &Channel {
+        self.conn.channel()
+    }
+
+    /// Run `cmd` and block until it exits. Before-sleep in particular must
+    /// finish before we release the inhibitor fd below, or logind may let
+    /// the machine suspend while the lock command is still starting up.
+    fn run(cmd: &(String, Vec<String>), what: &str) {
+        match std::process::Command::new(&cmd.0)
+            .args(&cmd.1)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .and_then(|mut child| child.wait())
+        {
+            Ok(_) => {}
+            Err(e) => eprintln!("Error running {} command: {:?}", what, e),
+        }
+    }
+
+    /// Drain and handle any pending bus messages. Call this whenever the fd
+    /// from `as_raw_fd` becomes readable. Runs the configured before-sleep
+    /// command and releases the inhibitor on `PrepareForSleep(true)`, and
+    /// runs the after-resume command and re-acquires a fresh inhibitor on
+    /// `PrepareForSleep(false)`.
+    pub fn handle_events(&mut self) {
+        let _ = self.channel().read_write(Some(Duration::from_millis(0)));
+
+        while let Some(msg) = self.channel().pop_message() {
+            self.handle_message(&msg);
+        }
+    }
+
+    fn handle_message(&mut self, msg: &Message) {
+        if msg.interface().as_deref() != Some(LOGIN1_MANAGER_IFACE)
+            || msg.member().as_deref() != Some("PrepareForSleep")
+        {
+            return;
+        }
+
+        let going_to_sleep: bool = match msg.read1() {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("Malformed PrepareForSleep signal: {:?}", e);
+                return;
+            }
+        };
+
+        if going_to_sleep {
+            if let Some(cmd) = self.before_sleep.clone() {
+                Self::run(&cmd, "before-sleep");
+            }
+            // Drop the inhibitor so the machine is actually allowed to sleep.
+            self.inhibitor = None;
+        } else {
+            if let Some(cmd) = self.after_resume.clone() {
+                Self::run(&cmd, "after-resume");
+            }
+            if let Err(e) = self.acquire() {
+                eprintln!("Cannot re-acquire logind sleep inhibitor: {:?}", e);
+            }
+        }
+    }
+}